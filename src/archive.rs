@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs::{read_to_string, write};
+
+use crate::error::MovieMailError;
+use crate::{Config, Movie};
+
+/// Storage backend for the set of movies moviemail has already seen.
+/// `record` upserts by TMDB id, and `load_seen_ids`/`all` are used to figure
+/// out what's new. The SQL backends upsert row by row, so concurrent runs
+/// don't clobber each other; the JSON backend merges by id but rewrites the
+/// whole file, so concurrent runs against it can lose each other's writes.
+pub trait ArchiveStore {
+    fn load_seen_ids(&self) -> Result<HashSet<u32>, MovieMailError>;
+    fn record(&self, movies: &[Movie]) -> Result<(), MovieMailError>;
+    fn all(&self) -> Result<Vec<Movie>, MovieMailError>;
+}
+
+pub struct JsonArchive {
+    path: String,
+}
+
+impl JsonArchive {
+    pub fn new(path: String) -> JsonArchive {
+        return JsonArchive { path };
+    }
+}
+
+impl ArchiveStore for JsonArchive {
+    fn load_seen_ids(&self) -> Result<HashSet<u32>, MovieMailError> {
+        return Ok(self.all()?.into_iter().map(|m| m.id).collect());
+    }
+
+    fn record(&self, movies: &[Movie]) -> Result<(), MovieMailError> {
+        let mut by_id: HashMap<u32, Movie> = self.all()?.into_iter().map(|m| (m.id, m)).collect();
+        for movie in movies {
+            by_id.insert(movie.id, movie.clone());
+        }
+
+        let movies_json = serde_json::to_string(&by_id.into_values().collect::<Vec<Movie>>())?;
+        write(&self.path, movies_json).map_err(MovieMailError::ArchiveIo)?;
+        return Ok(());
+    }
+
+    fn all(&self) -> Result<Vec<Movie>, MovieMailError> {
+        match read_to_string(&self.path) {
+            Ok(m) => Ok(serde_json::from_str(&*m)?),
+            Err(_) => Ok(vec![]),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+pub struct SqliteArchive {
+    conn: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteArchive {
+    pub fn new(path: &str) -> Result<SqliteArchive, MovieMailError> {
+        let conn = rusqlite::Connection::open(path).map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS movies (
+                id INTEGER PRIMARY KEY,
+                title TEXT NOT NULL,
+                overview TEXT NOT NULL,
+                poster_path TEXT,
+                release_date TEXT NOT NULL,
+                role TEXT,
+                person_name TEXT,
+                imdb_id TEXT,
+                runtime INTEGER
+            )",
+            (),
+        ).map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+
+        return Ok(SqliteArchive { conn });
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ArchiveStore for SqliteArchive {
+    fn load_seen_ids(&self) -> Result<HashSet<u32>, MovieMailError> {
+        let mut stmt = self.conn.prepare("SELECT id FROM movies")
+            .map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+        let ids = stmt.query_map((), |row| row.get(0))
+            .map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?
+            .filter_map(Result::ok)
+            .collect();
+
+        return Ok(ids);
+    }
+
+    fn record(&self, movies: &[Movie]) -> Result<(), MovieMailError> {
+        for movie in movies {
+            self.conn.execute(
+                "INSERT INTO movies (id, title, overview, poster_path, release_date, role, person_name, imdb_id, runtime)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                 ON CONFLICT(id) DO UPDATE SET
+                    title = excluded.title,
+                    overview = excluded.overview,
+                    poster_path = excluded.poster_path,
+                    release_date = excluded.release_date,
+                    role = excluded.role,
+                    person_name = excluded.person_name,
+                    imdb_id = excluded.imdb_id,
+                    runtime = excluded.runtime",
+                (&movie.id, &movie.title, &movie.overview, &movie.poster_path, &movie.release_date, &movie.role, &movie.person_name, &movie.imdb_id, &movie.runtime),
+            ).map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+        }
+
+        return Ok(());
+    }
+
+    fn all(&self) -> Result<Vec<Movie>, MovieMailError> {
+        let mut stmt = self.conn.prepare("SELECT id, title, overview, poster_path, release_date, role, person_name, imdb_id, runtime FROM movies")
+            .map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+        let movies = stmt.query_map((), |row| {
+            Ok(Movie {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                overview: row.get(2)?,
+                poster_path: row.get(3)?,
+                release_date: row.get(4)?,
+                role: row.get(5)?,
+                person_name: row.get(6)?,
+                imdb_id: row.get(7)?,
+                runtime: row.get(8)?,
+            })
+        }).map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?
+            .filter_map(Result::ok)
+            .collect();
+
+        return Ok(movies);
+    }
+}
+
+#[cfg(feature = "postgres")]
+pub struct PostgresArchive {
+    client: std::sync::Mutex<postgres::Client>,
+}
+
+#[cfg(feature = "postgres")]
+impl PostgresArchive {
+    pub fn new(connection_string: &str) -> Result<PostgresArchive, MovieMailError> {
+        let mut client = postgres::Client::connect(connection_string, postgres::NoTls)
+            .map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+        client.execute(
+            "CREATE TABLE IF NOT EXISTS movies (
+                id INTEGER PRIMARY KEY,
+                title TEXT NOT NULL,
+                overview TEXT NOT NULL,
+                poster_path TEXT,
+                release_date TEXT NOT NULL,
+                role TEXT,
+                person_name TEXT,
+                imdb_id TEXT,
+                runtime INTEGER
+            )",
+            &[],
+        ).map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+
+        return Ok(PostgresArchive { client: std::sync::Mutex::new(client) });
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl ArchiveStore for PostgresArchive {
+    fn load_seen_ids(&self) -> Result<HashSet<u32>, MovieMailError> {
+        let mut client = self.client.lock().unwrap();
+        let rows = client.query("SELECT id FROM movies", &[])
+            .map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+
+        return Ok(rows.into_iter().map(|r| r.get::<_, i32>(0) as u32).collect());
+    }
+
+    fn record(&self, movies: &[Movie]) -> Result<(), MovieMailError> {
+        let mut client = self.client.lock().unwrap();
+        for movie in movies {
+            client.execute(
+                "INSERT INTO movies (id, title, overview, poster_path, release_date, role, person_name, imdb_id, runtime)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                 ON CONFLICT (id) DO UPDATE SET
+                    title = excluded.title,
+                    overview = excluded.overview,
+                    poster_path = excluded.poster_path,
+                    release_date = excluded.release_date,
+                    role = excluded.role,
+                    person_name = excluded.person_name,
+                    imdb_id = excluded.imdb_id,
+                    runtime = excluded.runtime",
+                &[&(movie.id as i32), &movie.title, &movie.overview, &movie.poster_path, &movie.release_date, &movie.role, &movie.person_name, &movie.imdb_id, &movie.runtime.map(|r| r as i32)],
+            ).map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+        }
+
+        return Ok(());
+    }
+
+    fn all(&self) -> Result<Vec<Movie>, MovieMailError> {
+        let mut client = self.client.lock().unwrap();
+        let rows = client.query("SELECT id, title, overview, poster_path, release_date, role, person_name, imdb_id, runtime FROM movies", &[])
+            .map_err(|e| MovieMailError::ArchiveIo(std::io::Error::other(e)))?;
+
+        return Ok(rows.into_iter().map(|r| Movie {
+            id: r.get::<_, i32>(0) as u32,
+            title: r.get(1),
+            overview: r.get(2),
+            poster_path: r.get(3),
+            release_date: r.get(4),
+            role: r.get(5),
+            person_name: r.get(6),
+            imdb_id: r.get(7),
+            runtime: r.get::<_, Option<i32>>(8).map(|r| r as u32),
+        }).collect());
+    }
+}
+
+/// Builds the archive backend selected by `Config.archive_backend` ("json",
+/// "sqlite", or "postgres").
+pub fn build_archive_store(config: &Config, archive_path: &str) -> Result<Box<dyn ArchiveStore>, MovieMailError> {
+    match &*config.archive_backend {
+        "json" => Ok(Box::new(JsonArchive::new(archive_path.to_string()))),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(Box::new(SqliteArchive::new(archive_path)?)),
+        #[cfg(feature = "postgres")]
+        "postgres" => {
+            let connection_string = config.archive_database_url.as_ref()
+                .ok_or_else(|| MovieMailError::Config("archive_database_url is required when archive_backend = \"postgres\"".to_string()))?;
+            Ok(Box::new(PostgresArchive::new(connection_string)?))
+        }
+        other => Err(MovieMailError::Config(format!("unsupported archive_backend: {other}"))),
+    }
+}
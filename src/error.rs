@@ -0,0 +1,31 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MovieMailError {
+    #[error("error reading config file: {0}")]
+    ConfigRead(std::io::Error),
+
+    #[error("error parsing config file: {0}")]
+    ConfigParse(#[from] toml::de::Error),
+
+    #[error("error making http request: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("error deserializing response: {0}")]
+    Deserialize(#[from] serde_json::Error),
+
+    #[error("error sending email: {0}")]
+    Smtp(String),
+
+    #[error("error posting to mastodon: {0}")]
+    Mastodon(String),
+
+    #[error("error reading/writing archive: {0}")]
+    ArchiveIo(std::io::Error),
+
+    #[error("invalid config: {0}")]
+    Config(String),
+
+    #[error("error fetching poster: {0}")]
+    Poster(String),
+}
@@ -1,17 +1,43 @@
+// this codebase consistently favors explicit `return`s and `&*` reborrows
+// over the terser forms clippy suggests; keep those two lints off rather
+// than churning every function to match clippy's preferred style.
+#![allow(clippy::needless_return, clippy::explicit_auto_deref)]
+
+mod archive;
+mod error;
+mod notifier;
+
 use std::fs::{read_to_string, write};
 use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration as StdDuration;
 use core::option::Option;
+use chrono::{DateTime, Duration, Utc};
 use serde_derive::{Deserialize, Serialize};
-use futures::future::join_all;
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::{Message, SmtpTransport, Transport};
-use lettre::message::MultiPart;
+use futures::stream::{self, StreamExt};
+use rand::Rng;
+use lettre::Message;
+use lettre::message::{Attachment, MultiPart, header::ContentType};
 use shellexpand::tilde;
-use log::info;
+use log::{info, warn};
+
+use archive::build_archive_store;
+use error::MovieMailError;
+use notifier::{MastodonConfig, MastodonNotifier, Notifier, SmtpNotifier};
+
+fn default_true() -> bool {
+    return true;
+}
 
 #[derive(Deserialize)]
-struct Config {
+pub(crate) struct Config {
     archive_path: String,
+    archive_backend: String,
+    #[serde(default)]
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    archive_database_url: Option<String>,
+    cache_path: String,
+    cache_ttl_hours: i64,
     dry_run: bool,
     api_key: String,
     to: String,
@@ -20,165 +46,504 @@ struct Config {
     username: String,
     password: String,
     smtp: String,
-    directors: HashMap<String, String>,
+    people: HashMap<String, String>,
+    roles: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    include_posters: bool,
+    #[serde(default = "default_true")]
+    email: bool,
+    #[serde(default)]
+    mastodon: Option<MastodonConfig>,
+    #[serde(default = "default_max_concurrency")]
+    max_concurrency: usize,
+    #[serde(default = "default_max_retries")]
+    max_retries: u32,
 }
 
-#[derive(Deserialize, Serialize, Clone)]
-struct Movie {
+fn default_max_concurrency() -> usize {
+    return 5;
+}
+
+fn default_max_retries() -> u32 {
+    return 4;
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub(crate) struct Movie {
     id: u32,
     title: String,
     overview: String,
     poster_path: Option<String>,
     release_date: String,
-    job: Option<String>,
-    director_name: Option<String>,
+    role: Option<String>,
+    person_name: Option<String>,
     imdb_id: Option<String>,
+    runtime: Option<u32>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Debug)]
 struct MovieDetails {
     id: u32,
     imdb_id: Option<String>,
     runtime: Option<u32>,
 }
 
+#[derive(Deserialize)]
+struct CrewCredit {
+    id: u32,
+    title: String,
+    overview: String,
+    poster_path: Option<String>,
+    release_date: String,
+    job: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CastCredit {
+    id: u32,
+    title: String,
+    overview: String,
+    poster_path: Option<String>,
+    release_date: String,
+}
+
 #[derive(Deserialize)]
 struct Credits {
-    crew: Vec<Movie>,
+    crew: Vec<CrewCredit>,
+    cast: Vec<CastCredit>,
 }
 
-fn read_config(path: &str) -> Config {
-    let config_str = read_to_string(path).expect("error reading config.toml");
-    let config: Config = toml::from_str(&*config_str).expect("error deserializing config.toml");
+#[derive(Deserialize, Serialize, Clone)]
+struct CacheEntry {
+    fetched_at: DateTime<Utc>,
+    body: String,
+}
 
-    return config;
+/// Persistent cache of raw TMDB JSON responses, keyed by request path
+/// (e.g. `person/123/movie_credits`, `movie/456`). Entries not touched
+/// during the run are dropped when the cache is saved.
+struct Cache {
+    ttl_hours: i64,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    touched: Mutex<HashSet<String>>,
 }
 
-fn read_archive(path: &str) -> Vec<Movie> {
-    match read_to_string(path) {
-        Ok(m) => { serde_json::from_str(&*m).expect("error reading archive.json") }
-        Err(_) => { vec![] }
+impl Cache {
+    fn load(path: &str, ttl_hours: i64) -> Result<Cache, MovieMailError> {
+        let entries = match read_to_string(path) {
+            Ok(c) => serde_json::from_str(&*c)?,
+            Err(_) => HashMap::new(),
+        };
+
+        return Ok(Cache {
+            ttl_hours,
+            entries: Mutex::new(entries),
+            touched: Mutex::new(HashSet::new()),
+        });
+    }
+
+    /// Returns the cached body for `key` if present and, when `ttl_hours`
+    /// is `Some`, younger than that TTL. Pass `None` for responses that
+    /// are effectively immutable (e.g. movie details).
+    fn get(&self, key: &str, ttl_hours: Option<i64>) -> Option<String> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+
+        if let Some(ttl) = ttl_hours {
+            if Utc::now() - entry.fetched_at > Duration::hours(ttl) {
+                return None;
+            }
+        }
+
+        self.touched.lock().unwrap().insert(key.to_string());
+        return Some(entry.body.clone());
+    }
+
+    fn put(&self, key: &str, body: String) {
+        self.entries.lock().unwrap().insert(key.to_string(), CacheEntry { fetched_at: Utc::now(), body });
+        self.touched.lock().unwrap().insert(key.to_string());
+    }
+
+    fn save(&self, path: &str) -> Result<(), MovieMailError> {
+        let touched = self.touched.lock().unwrap();
+        let entries = self.entries.lock().unwrap();
+        let pruned: HashMap<&String, &CacheEntry> = entries
+            .iter()
+            .filter(|(k, _)| touched.contains(*k))
+            .collect();
+
+        let cache_json = serde_json::to_string(&pruned)?;
+        write(path, cache_json).map_err(MovieMailError::ArchiveIo)?;
+        return Ok(());
     }
 }
 
-fn write_archive(movies: &Vec<Movie>, path: &str) {
-    let movies_json = serde_json::to_string(movies).expect("error in serializing movies for archive");
-    write(path, movies_json).expect("error writing archive.json");
+fn read_config(path: &str) -> Result<Config, MovieMailError> {
+    let config_str = read_to_string(path).map_err(MovieMailError::ConfigRead)?;
+    let config: Config = toml::from_str(&*config_str)?;
+
+    return Ok(config);
 }
 
-async fn fetch_director_credits(director_id: String, director_name: String, api_key: &String) -> Vec<Movie> {
-    // https://developers.themoviedb.org/3/people/get-person-movie-credits
-    let url = format!("https://api.themoviedb.org/3/person/{director_id}/movie_credits?api_key={api_key}&language=en-US");
-    let resp = reqwest::get(url).await.expect("error fetching from tmdb").text().await.unwrap();
-    let mut credits: Credits = serde_json::from_str(&*resp).expect("error deserializing movie credits");
+/// Backoff delay before retry attempt `attempt` (0-indexed): 250ms, 500ms,
+/// 1s, ... capped at 8s, with up to 25% jitter to avoid thundering-herd
+/// retries against TMDB.
+fn backoff_delay(attempt: u32) -> StdDuration {
+    let capped_ms = (250u64.saturating_mul(1u64 << attempt.min(16))).min(8_000);
+    let jitter_ms = rand::thread_rng().gen_range(0..=(capped_ms / 4));
+    return StdDuration::from_millis(capped_ms + jitter_ms);
+}
 
-    for credit in &mut credits.crew {
-        credit.director_name = Some(director_name.clone());
+/// GETs `url`, retrying on TMDB rate limiting (honoring `Retry-After`) or
+/// transient request errors with exponential backoff, up to `max_retries`.
+async fn get_with_retry(url: &str, max_retries: u32) -> Result<String, MovieMailError> {
+    let mut attempt = 0;
+    loop {
+        match reqwest::get(url).await {
+            Ok(resp) if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS && attempt < max_retries => {
+                let delay = resp.headers().get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|s| s.parse::<u64>().ok())
+                    .map(StdDuration::from_secs)
+                    .unwrap_or_else(|| backoff_delay(attempt));
+                warn!("tmdb rate limited, retrying {} in {:?} (attempt {}/{})", url, delay, attempt + 1, max_retries);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Ok(resp) if resp.status().is_server_error() && attempt < max_retries => {
+                let delay = backoff_delay(attempt);
+                warn!("tmdb request to {} returned {}, retrying in {:?} (attempt {}/{})", url, resp.status(), delay, attempt + 1, max_retries);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Ok(resp) => return Ok(resp.error_for_status()?.text().await?),
+            Err(e) if attempt < max_retries => {
+                let delay = backoff_delay(attempt);
+                warn!("tmdb request to {} failed ({}), retrying in {:?} (attempt {}/{})", url, e, delay, attempt + 1, max_retries);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
     }
+}
 
-    return credits.crew;
+async fn fetch_person_credits(person_id: String, person_name: String, api_key: &String, cache: &Cache, max_retries: u32) -> Result<Vec<Movie>, MovieMailError> {
+    // https://developers.themoviedb.org/3/people/get-person-movie-credits
+    let cache_key = format!("person/{person_id}/movie_credits");
+    let resp = match cache.get(&cache_key, Some(cache.ttl_hours)) {
+        Some(body) => body,
+        None => {
+            let url = format!("https://api.themoviedb.org/3/person/{person_id}/movie_credits?api_key={api_key}&language=en-US");
+            let body = get_with_retry(&url, max_retries).await?;
+            cache.put(&cache_key, body.clone());
+            body
+        }
+    };
+    let credits: Credits = serde_json::from_str(&*resp)?;
+
+    let crew_movies = credits.crew.into_iter().map(|c| Movie {
+        id: c.id,
+        title: c.title,
+        overview: c.overview,
+        poster_path: c.poster_path,
+        release_date: c.release_date,
+        role: c.job,
+        person_name: Some(person_name.clone()),
+        imdb_id: None,
+        runtime: None,
+    });
+
+    // cast credits don't carry a job/department of their own; they're always an acting role
+    let cast_movies = credits.cast.into_iter().map(|c| Movie {
+        id: c.id,
+        title: c.title,
+        overview: c.overview,
+        poster_path: c.poster_path,
+        release_date: c.release_date,
+        role: Some("Acting".to_string()),
+        person_name: Some(person_name.clone()),
+        imdb_id: None,
+        runtime: None,
+    });
+
+    return Ok(crew_movies.chain(cast_movies).collect());
 }
 
-async fn fetch_movie_details(movie_id: u32, api_key: &String) -> MovieDetails {
+async fn fetch_movie_details(movie_id: u32, api_key: &String, cache: &Cache, max_retries: u32) -> Result<MovieDetails, MovieMailError> {
     // https://developers.themoviedb.org/3/movies/get-movie-details
-    let url = format!("https://api.themoviedb.org/3/movie/{movie_id}?api_key={api_key}&language=en-US");
-    let resp = reqwest::get(url).await.expect("error fetching from tmdb").text().await.unwrap();
-    let movie_details: MovieDetails = serde_json::from_str(&*resp).expect("error deserializing movie details");
-    return movie_details;
+    let cache_key = format!("movie/{movie_id}");
+    let resp = match cache.get(&cache_key, None) {
+        Some(body) => body,
+        None => {
+            let url = format!("https://api.themoviedb.org/3/movie/{movie_id}?api_key={api_key}&language=en-US");
+            let body = get_with_retry(&url, max_retries).await?;
+            cache.put(&cache_key, body.clone());
+            body
+        }
+    };
+    let movie_details: MovieDetails = serde_json::from_str(&*resp)?;
+    return Ok(movie_details);
 }
 
-fn create_message_body(movies: Vec<Movie>) -> (String, String) {
+// TMDB w200 poster thumbnails are small; this just guards against an oversized/unexpected response.
+const MAX_POSTER_BYTES: usize = 200 * 1024;
+
+fn poster_cid(movie_id: u32) -> String {
+    return format!("poster-{movie_id}");
+}
+
+/// GETs `url` like `get_with_retry`, but for binary bodies, rejecting
+/// responses over `MAX_POSTER_BYTES` instead of buffering them in full.
+async fn get_bytes_with_retry(url: &str, max_retries: u32) -> Result<Vec<u8>, MovieMailError> {
+    let mut attempt = 0;
+    loop {
+        match reqwest::get(url).await {
+            Ok(resp) if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS && attempt < max_retries => {
+                let delay = resp.headers().get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|s| s.parse::<u64>().ok())
+                    .map(StdDuration::from_secs)
+                    .unwrap_or_else(|| backoff_delay(attempt));
+                warn!("tmdb rate limited, retrying {} in {:?} (attempt {}/{})", url, delay, attempt + 1, max_retries);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Ok(resp) if resp.status().is_server_error() && attempt < max_retries => {
+                let delay = backoff_delay(attempt);
+                warn!("tmdb request to {} returned {}, retrying in {:?} (attempt {}/{})", url, resp.status(), delay, attempt + 1, max_retries);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Ok(resp) => {
+                let resp = resp.error_for_status()?;
+                if let Some(len) = resp.content_length() {
+                    if len as usize > MAX_POSTER_BYTES {
+                        return Err(MovieMailError::Poster(format!("{url} is {len} bytes, over the {MAX_POSTER_BYTES} byte cap")));
+                    }
+                }
+
+                // stream the body so an oversized response is rejected as soon as it crosses
+                // the cap, instead of being buffered in full first.
+                let mut body = resp.bytes_stream();
+                let mut bytes = Vec::new();
+                while let Some(chunk) = body.next().await {
+                    bytes.extend_from_slice(&chunk?);
+                    if bytes.len() > MAX_POSTER_BYTES {
+                        return Err(MovieMailError::Poster(format!("{url} exceeded the {MAX_POSTER_BYTES} byte cap")));
+                    }
+                }
+                return Ok(bytes);
+            }
+            Err(e) if attempt < max_retries => {
+                let delay = backoff_delay(attempt);
+                warn!("tmdb request to {} failed ({}), retrying in {:?} (attempt {}/{})", url, e, delay, attempt + 1, max_retries);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+async fn fetch_poster(poster_path: &str, max_retries: u32) -> Result<Vec<u8>, MovieMailError> {
+    let url = format!("https://image.tmdb.org/t/p/w200{poster_path}");
+    return get_bytes_with_retry(&url, max_retries).await;
+}
+
+pub(crate) async fn fetch_posters(movies: &[Movie], max_concurrency: usize, max_retries: u32) -> HashMap<u32, Vec<u8>> {
+    let poster_paths: Vec<(u32, String)> = movies
+        .iter()
+        .filter_map(|m| m.poster_path.as_ref().map(|p| (m.id, p.clone())))
+        .collect();
+    let poster_fetches = poster_paths
+        .into_iter()
+        .map(|(id, path)| async move { (id, fetch_poster(&path, max_retries).await) });
+
+    let mut posters = HashMap::new();
+    let mut results = stream::iter(poster_fetches).buffer_unordered(max_concurrency);
+    while let Some((id, result)) = results.next().await {
+        match result {
+            Ok(bytes) => { posters.insert(id, bytes); }
+            Err(e) => warn!("skipping poster for movie {}: {}", id, e),
+        }
+    }
+
+    return posters;
+}
+
+fn create_message_body(movies: &[Movie], posters: &HashMap<u32, Vec<u8>>) -> (String, String) {
     let mut message_plain = String::new();
     let mut message_html = String::new();
     for movie in movies {
-        let title = movie.title;
-        let director = movie.director_name.unwrap();
+        let title = &movie.title;
+        let person = movie.person_name.as_deref().unwrap_or("");
 
-        let link = match movie.imdb_id {
+        let link = match &movie.imdb_id {
             Some(imdb_id) => { format!("https://www.imdb.com/title/{}", imdb_id) }
             None => { format!("https://www.themoviedb.org/movie/{}", movie.id) }
         };
 
-        message_plain.push_str(&*format!("{} - {} - {}\n", link, title, director));
-        message_html.push_str(&*format!("<p><a href=\"{}\">{} - {}</a></p>", link, title, director));
+        message_plain.push_str(&*format!("{} - {} - {}\n", link, title, person));
+
+        let poster_img = match posters.get(&movie.id) {
+            Some(_) => format!("<img src=\"cid:{}\" alt=\"\"><br>", poster_cid(movie.id)),
+            None => String::new(),
+        };
+        message_html.push_str(&*format!("<p>{}<a href=\"{}\">{} - {}</a></p>", poster_img, link, title, person));
     }
     return (message_plain, message_html);
 }
 
-fn create_email(movies: Vec<Movie>, to: String, from: String, subject: String) -> Message {
-    let (plain, html) = create_message_body(movies);
+pub(crate) fn create_email(movies: Vec<Movie>, to: String, from: String, subject: String, posters: &HashMap<u32, Vec<u8>>) -> Result<Message, MovieMailError> {
+    let (plain, html) = create_message_body(&movies, posters);
+
+    let body = if posters.is_empty() {
+        MultiPart::alternative_plain_html(plain, html)
+    } else {
+        let mut related = MultiPart::related().multipart(MultiPart::alternative_plain_html(plain, html));
+        for movie in &movies {
+            if let Some(bytes) = posters.get(&movie.id) {
+                related = related.singlepart(
+                    Attachment::new_inline(poster_cid(movie.id)).body(bytes.clone(), ContentType::parse("image/jpeg").unwrap())
+                );
+            }
+        }
+        related
+    };
 
     return Message::builder()
-        .to(to.parse().unwrap())
-        .from(from.parse().unwrap())
+        .to(to.parse().map_err(|e: lettre::address::AddressError| MovieMailError::Smtp(e.to_string()))?)
+        .from(from.parse().map_err(|e: lettre::address::AddressError| MovieMailError::Smtp(e.to_string()))?)
         .subject(subject)
-        .multipart(MultiPart::alternative_plain_html(
-            plain,
-            html
-        ))
-        .unwrap();
+        .multipart(body)
+        .map_err(|e| MovieMailError::Smtp(e.to_string()));
+}
+
+fn build_notifiers(config: &Config) -> Vec<Box<dyn Notifier>> {
+    let mut notifiers: Vec<Box<dyn Notifier>> = Vec::new();
+
+    if config.email {
+        notifiers.push(Box::new(SmtpNotifier {
+            to: config.to.clone(),
+            from: config.from.clone(),
+            subject: config.subject.clone(),
+            username: config.username.clone(),
+            password: config.password.clone(),
+            smtp: config.smtp.clone(),
+            include_posters: config.include_posters,
+            max_concurrency: config.max_concurrency,
+            max_retries: config.max_retries,
+        }));
+    }
+
+    if let Some(mastodon) = &config.mastodon {
+        notifiers.push(Box::new(MastodonNotifier::new(mastodon.clone())));
+    }
+
+    return notifiers;
 }
 
 
 #[tokio::main]
-async fn main() {
+async fn main() -> Result<(), MovieMailError> {
     let env = env_logger::Env::default().filter_or("LOG_LEVEL", "info");
     env_logger::init_from_env(env);
 
     let config_path = &tilde("~/.config/moviemail/config.toml");
     info!("reading config from {}", config_path);
-    let config = read_config(&config_path);
+    let config = read_config(config_path)?;
 
     let archive_path = &tilde(&config.archive_path);
-    info!("reading archive from {}", archive_path);
-    let archive = read_archive(&archive_path);
-    let archive_set: HashSet<u32> = archive.into_iter().map(|a| a.id).collect();
+    info!("reading archive from {} (backend: {})", archive_path, config.archive_backend);
+    let archive_store = build_archive_store(&config, archive_path)?;
+    let archive_set = archive_store.load_seen_ids()?;
 
     info!("loaded {} movies from archive", archive_set.len());
 
-    // for each director call tmdb async
-    let movie_futures = config.directors
+    let cache_path = &tilde(&config.cache_path);
+    info!("reading cache from {}", cache_path);
+    let cache = Cache::load(cache_path, config.cache_ttl_hours)?;
+
+    // for each tracked person call tmdb async, bounded to max_concurrency in flight at once
+    let movie_futures = config.people
         .clone()
         .into_iter()
-        .map(|d| fetch_director_credits(d.0, d.1, &config.api_key));
+        .map(|d| fetch_person_credits(d.0, d.1, &config.api_key, &cache, config.max_retries));
 
-    // collect results and filter to just directing roles and movies with release dates later than 2023
-    let mut movies: HashMap<u32, Movie> = join_all(movie_futures)
+    // collect results and filter to each person's allowed roles and movies with release dates,
+    // logging and skipping any person whose fetch failed rather than aborting the whole run
+    let (ok_credits, failed_credits): (Vec<_>, Vec<_>) = stream::iter(movie_futures)
+        .buffer_unordered(config.max_concurrency)
+        .collect::<Vec<_>>()
         .await
         .into_iter()
-        .flatten()
-        .filter(|m| m.job == Some("Director".to_string()))
-        .filter(|m| m.release_date != "".to_string())
+        .partition(Result::is_ok);
+
+    for err in failed_credits.into_iter().map(Result::unwrap_err) {
+        warn!("skipping person whose credits failed to fetch: {}", err);
+    }
+
+    let mut movies: HashMap<u32, Movie> = ok_credits
+        .into_iter()
+        .flat_map(Result::unwrap)
+        .filter(|m| {
+            let allowed_roles = m.person_name.as_ref().and_then(|n| config.roles.get(n));
+            match (allowed_roles, &m.role) {
+                (Some(allowed), Some(role)) => allowed.contains(role),
+                _ => false,
+            }
+        })
+        .filter(|m| !m.release_date.is_empty())
         .map(|m| (m.id, m))
         .collect();
 
-    info!("fetched {} movies from {} directors", movies.len(), config.directors.len());
+    info!("fetched {} movies from {} tracked people", movies.len(), config.people.len());
 
     // filter out movies previously archived
     let mut new_movies: Vec<Movie> = movies
         .values()
-        .cloned()
         .filter(|m| !archive_set.contains(&m.id))
+        .cloned()
         .collect();
 
     info!("{} unfiltered new movies", new_movies.len());
 
-    // get details for new_movies and store async
+    // get details for new_movies and store async, bounded to max_concurrency in flight at once
     let movie_details_futures = new_movies
         .clone()
         .into_iter()
-        .map(|m| fetch_movie_details(m.id, &config.api_key));
+        .map(|m| fetch_movie_details(m.id, &config.api_key, &cache, config.max_retries));
 
-    let movie_details: HashMap<u32, MovieDetails> = join_all(movie_details_futures)
+    let (ok_details, failed_details): (Vec<_>, Vec<_>) = stream::iter(movie_details_futures)
+        .buffer_unordered(config.max_concurrency)
+        .collect::<Vec<_>>()
         .await
         .into_iter()
+        .partition(Result::is_ok);
+
+    for err in failed_details.into_iter().map(Result::unwrap_err) {
+        warn!("skipping movie whose details failed to fetch: {}", err);
+    }
+
+    let movie_details: HashMap<u32, MovieDetails> = ok_details
+        .into_iter()
+        .map(Result::unwrap)
         .map(|m| (m.id, m))
         .collect();
 
     // get details for new_movies and store in HashMap
     let mut invalid_new_movies: HashSet<u32> = HashSet::new();
     for movie in &mut new_movies {
-        let details = movie_details.get(&movie.id).unwrap();
+        let details = match movie_details.get(&movie.id) {
+            Some(details) => details,
+            None => {
+                // details failed to fetch above and were already logged; drop the movie
+                movies.remove(&movie.id);
+                invalid_new_movies.insert(movie.id);
+                continue;
+            }
+        };
         let runtime = details.runtime.unwrap_or(0);
 
         // if no imdb, ignore it
@@ -191,6 +556,11 @@ async fn main() {
             }
         }
 
+        movie.runtime = Some(runtime);
+        if let Some(m) = movies.get_mut(&movie.id) {
+            m.runtime = Some(runtime);
+        }
+
         if runtime == 0 {
             // remove from movies
             movies.remove(&movie.id);
@@ -203,36 +573,25 @@ async fn main() {
     }
 
     // remove invalid movies from new_movies
-    new_movies = new_movies
-        .into_iter()
-        .filter(|m| !invalid_new_movies.contains(&m.id))
-        .collect();
+    new_movies.retain(|m| !invalid_new_movies.contains(&m.id));
 
     info!("{} valid new movies", new_movies.len());
 
-    if new_movies.len() > 0 {
-        if config.dry_run {
-            for movie in new_movies {
-                println!("{:?}, {:?}, {:?}, {:?}", movie.title, movie.director_name.unwrap(), movie.release_date, movie.imdb_id.unwrap_or("".to_string()));
-            }
-        } else {
-            info!("preparing email");
-            let email = create_email(new_movies, config.to, config.from, config.subject);
-            let creds = Credentials::new(config.username, config.password);
-
-            let mailer = SmtpTransport::relay(&*config.smtp)
-                .unwrap()
-                .credentials(creds)
-                .build();
-
-            match mailer.send(&email) {
-                Ok(_) => info!("email sent successfully!"),
-                Err(e) => panic!("Could not send email: {:?}", e),
-            }
+    if !new_movies.is_empty() {
+        let notifiers = build_notifiers(&config);
+        for notifier in &notifiers {
+            info!("notifying via {}", notifier.name());
+            notifier.notify(&new_movies, config.dry_run).await?;
         }
     }
 
-    // write all movies to archive file
+    // record all tracked movies to the archive store
     info!("writing {} movies to archive", movies.len());
-    write_archive(&movies.values().cloned().collect(), &archive_path);
+    let movies_to_record: Vec<Movie> = movies.values().cloned().collect();
+    archive_store.record(&movies_to_record)?;
+
+    info!("writing cache to {}", cache_path);
+    cache.save(cache_path)?;
+
+    return Ok(());
 }
\ No newline at end of file
@@ -0,0 +1,161 @@
+use async_trait::async_trait;
+use reqwest::multipart;
+use serde_derive::Deserialize;
+
+use crate::error::MovieMailError;
+use crate::{create_email, fetch_posters, Movie};
+
+#[derive(Deserialize, Clone)]
+pub(crate) struct MastodonConfig {
+    instance_url: String,
+    access_token: String,
+    visibility: String,
+}
+
+/// A delivery channel for newly-detected movies. `dry_run` notifiers
+/// should describe what they would have sent instead of sending it.
+#[async_trait]
+pub trait Notifier {
+    fn name(&self) -> &str;
+    async fn notify(&self, movies: &[Movie], dry_run: bool) -> Result<(), MovieMailError>;
+}
+
+pub struct SmtpNotifier {
+    pub to: String,
+    pub from: String,
+    pub subject: String,
+    pub username: String,
+    pub password: String,
+    pub smtp: String,
+    pub include_posters: bool,
+    pub max_concurrency: usize,
+    pub max_retries: u32,
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &str {
+        return "email";
+    }
+
+    async fn notify(&self, movies: &[Movie], dry_run: bool) -> Result<(), MovieMailError> {
+        if dry_run {
+            for movie in movies {
+                println!("[email] would send: {:?}, {:?}, {:?}", movie.title, movie.person_name, movie.release_date);
+            }
+            return Ok(());
+        }
+
+        let posters = if self.include_posters {
+            fetch_posters(movies, self.max_concurrency, self.max_retries).await
+        } else {
+            std::collections::HashMap::new()
+        };
+
+        let email = create_email(movies.to_vec(), self.to.clone(), self.from.clone(), self.subject.clone(), &posters)?;
+
+        let creds = lettre::transport::smtp::authentication::Credentials::new(self.username.clone(), self.password.clone());
+        let mailer = lettre::SmtpTransport::relay(&self.smtp)
+            .map_err(|e| MovieMailError::Smtp(e.to_string()))?
+            .credentials(creds)
+            .build();
+
+        use lettre::Transport;
+        mailer.send(&email).map_err(|e| MovieMailError::Smtp(e.to_string()))?;
+        log::info!("email sent successfully!");
+
+        return Ok(());
+    }
+}
+
+pub struct MastodonNotifier {
+    config: MastodonConfig,
+}
+
+impl MastodonNotifier {
+    pub fn new(config: MastodonConfig) -> MastodonNotifier {
+        return MastodonNotifier { config };
+    }
+
+    fn status_text(movie: &Movie) -> String {
+        let person = movie.person_name.as_deref().unwrap_or("unknown");
+        let link = match &movie.imdb_id {
+            Some(imdb_id) => format!("https://www.imdb.com/title/{}", imdb_id),
+            None => format!("https://www.themoviedb.org/movie/{}", movie.id),
+        };
+
+        return format!("{}\n{}\n{}\n{}", movie.title, person, movie.release_date, link);
+    }
+
+    async fn upload_media(&self, poster_path: &str) -> Result<String, MovieMailError> {
+        let url = format!("https://image.tmdb.org/t/p/w500{poster_path}");
+        let bytes = reqwest::get(url).await?.bytes().await?;
+
+        let client = reqwest::Client::new();
+        let form = multipart::Form::new().part("file", multipart::Part::bytes(bytes.to_vec()).file_name("poster.jpg"));
+
+        let resp: serde_json::Value = client
+            .post(format!("{}/api/v2/media", self.config.instance_url))
+            .bearer_auth(&self.config.access_token)
+            .multipart(form)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        return Ok(resp["id"].as_str().unwrap_or_default().to_string());
+    }
+
+    async fn post_status(&self, movie: &Movie) -> Result<(), MovieMailError> {
+        let media_ids = match &movie.poster_path {
+            Some(poster_path) => vec![self.upload_media(poster_path).await?],
+            None => vec![],
+        };
+
+        let mut form = vec![
+            ("status", Self::status_text(movie)),
+            ("visibility", self.config.visibility.clone()),
+        ];
+        form.extend(media_ids.iter().map(|id| ("media_ids[]", id.clone())));
+
+        let client = reqwest::Client::new();
+        client
+            .post(format!("{}/api/v1/statuses", self.config.instance_url))
+            .bearer_auth(&self.config.access_token)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        return Ok(());
+    }
+}
+
+#[async_trait]
+impl Notifier for MastodonNotifier {
+    fn name(&self) -> &str {
+        return "mastodon";
+    }
+
+    async fn notify(&self, movies: &[Movie], dry_run: bool) -> Result<(), MovieMailError> {
+        let mut failed_titles = Vec::new();
+        for movie in movies {
+            if dry_run {
+                println!("[mastodon] would post: {}", Self::status_text(movie).replace('\n', " | "));
+                continue;
+            }
+
+            if let Err(e) = self.post_status(movie).await {
+                log::warn!("could not post {} to mastodon: {}", movie.title, e);
+                failed_titles.push(movie.title.clone());
+            }
+        }
+
+        if !failed_titles.is_empty() {
+            // a movie we failed to post must not be archived as "seen", or it's lost for good
+            return Err(MovieMailError::Mastodon(format!("failed to post: {}", failed_titles.join(", "))));
+        }
+
+        return Ok(());
+    }
+}